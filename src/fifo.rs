@@ -1,28 +1,62 @@
 use std::borrow::Borrow;
-use std::hash::Hash;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant};
 
-use linked_hash_map_rs::LinkedHashMap;
+use crate::linked_map::LinkedHashMap;
 
-pub struct FIFOCache<K, V> {
+pub struct FIFOCache<K, V, S = RandomState> {
     capacity: usize,
-    map: LinkedHashMap<K, V>,
+    map: LinkedHashMap<K, (Instant, V), S>,
+    ttl: Option<Duration>,
 
     hits: u64,
     misses: u64,
 }
 
-impl<K, V> FIFOCache<K, V>
+impl<K, V> FIFOCache<K, V, RandomState>
 where
     K: Hash + Eq,
 {
-    pub fn new(capacity: usize) -> Self {
+    /// `capacity` is a [`NonZeroUsize`] so a zero-capacity cache (which can
+    /// never hold an entry) is rejected at construction rather than
+    /// panicking on the first `put`.
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self::with_hasher(capacity, RandomState::default())
+    }
+
+    /// Create a cache whose entries expire `ttl` after they're inserted.
+    /// A `get`/`get_mut` against a stale entry is treated as a miss and the
+    /// entry is dropped.
+    pub fn with_ttl(capacity: NonZeroUsize, ttl: Duration) -> Self {
+        let mut this = Self::new(capacity);
+        this.ttl = Some(ttl);
+        this
+    }
+}
+
+impl<K, V, S> FIFOCache<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn with_hasher(capacity: NonZeroUsize, hasher: S) -> Self {
+        let capacity = capacity.get();
         FIFOCache {
             capacity,
-            map: LinkedHashMap::with_capacity(capacity),
-            ..Default::default()
+            map: LinkedHashMap::with_capacity_and_hasher(capacity, hasher),
+            ttl: None,
+            hits: 0,
+            misses: 0,
         }
     }
 
+    #[inline]
+    fn is_expired(&self, inserted: Instant) -> bool {
+        matches!(self.ttl, Some(ttl) if inserted.elapsed() >= ttl)
+    }
+
     /// Take an element out of the cache
     #[inline]
     pub fn take<Q: ?Sized>(&mut self, key: &Q) -> Option<(K, V)>
@@ -30,7 +64,7 @@ where
         K: Borrow<Q>,
         Q: Hash + Eq,
     {
-        self.map.remove(key)
+        self.map.remove(key).map(|(k, (_, v))| (k, v))
     }
 
     #[inline]
@@ -39,13 +73,20 @@ where
         K: Borrow<Q>,
         Q: Hash + Eq,
     {
-        if let Some(value) = self.map.get(key) {
-            self.hits += 1;
-            Some(value)
-        } else {
+        let expired = match self.map.get(key) {
+            Some((inserted, _)) => self.is_expired(*inserted),
+            None => {
+                self.misses += 1;
+                return None;
+            }
+        };
+        if expired {
+            self.map.remove(key);
             self.misses += 1;
-            None
+            return None;
         }
+        self.hits += 1;
+        self.map.get(key).map(|(_, v)| v)
     }
 
     #[inline]
@@ -54,13 +95,20 @@ where
         K: Borrow<Q>,
         Q: Hash + Eq,
     {
-        if let Some(value) = self.map.get_mut(key) {
-            self.hits += 1;
-            Some(value)
-        } else {
+        let expired = match self.map.get(key) {
+            Some((inserted, _)) => self.is_expired(*inserted),
+            None => {
+                self.misses += 1;
+                return None;
+            }
+        };
+        if expired {
+            self.map.remove(key);
             self.misses += 1;
-            None
+            return None;
         }
+        self.hits += 1;
+        self.map.get_mut(key).map(|(_, v)| v)
     }
 
     /// Move the element to the back of the queue and return it
@@ -70,14 +118,19 @@ where
         K: Borrow<Q>,
         Q: Hash + Eq,
     {
-        self.map.move_to_back(&key)
+        self.map.move_to_back(&key).map(|(k, (_, v))| (k, v))
     }
 
+    /// Evict the oldest entry if the cache is at capacity.
+    ///
+    /// `capacity` is always at least 1 (enforced by `NonZeroUsize` at
+    /// construction), so once the cache is full there is always something
+    /// at the front to pop — no panic path needed.
     #[inline]
     fn check_size(&mut self) -> Option<(K, V)> {
-        debug_assert!(self.len() <= self.capacity(), "out of capacity");
+        debug_assert!(self.len() <= self.capacity());
         if self.len() >= self.capacity() {
-            self.map.pop_front().or_else(|| panic!("out of capacity"))
+            self.map.pop_front().map(|(k, (_, v))| (k, v))
         } else {
             None
         }
@@ -88,7 +141,7 @@ where
     #[inline]
     pub fn put(&mut self, key: K, value: V) -> Option<(K, V)> {
         let res = self.check_size();
-        self.map.push_back(key, value);
+        self.map.push_back(key, (Instant::now(), value));
         res
     }
 
@@ -97,7 +150,21 @@ where
     #[inline]
     pub fn put_and_return(&mut self, key: K, value: V) -> (&K, &V) {
         self.check_size();
-        self.map.push_back_and_return(key, value)
+        let (k, (_, v)) = self.map.push_back_and_return(key, (Instant::now(), value));
+        (k, v)
+    }
+
+    /// Like [`put_and_return`](Self::put_and_return), but also hands back
+    /// the evicted entry instead of discarding it the way `put_and_return`
+    /// does.
+    ///
+    /// Used by [`crate::lru_2q::Lru2qCache`], which needs to know exactly
+    /// what got evicted so it can move the key into its ghost queue.
+    #[inline]
+    pub(crate) fn put_capturing_evicted(&mut self, key: K, value: V) -> (Option<(K, V)>, &K, &V) {
+        let evicted = self.check_size();
+        let (k, (_, v)) = self.map.push_back_and_return(key, (Instant::now(), value));
+        (evicted, k, v)
     }
 
     /// Get the actual size of the cache
@@ -112,6 +179,67 @@ where
         self.capacity
     }
 
+    /// Change the capacity of the cache.
+    ///
+    /// Shrinking pops entries from the front (the oldest ones) until the
+    /// cache fits the new capacity, returning whatever got evicted. Growing
+    /// just raises the bound; the map grows on its own as entries are added.
+    ///
+    /// `new_cap` is a [`NonZeroUsize`] for the same reason the constructors
+    /// are: a zero capacity would let the next `put` overfill the cache and
+    /// reopen the `debug_assert!` panic in [`check_size`](Self::check_size)
+    /// on the `put` after it.
+    pub fn set_capacity(&mut self, new_cap: NonZeroUsize) -> Vec<(K, V)> {
+        let new_cap = new_cap.get();
+        let mut evicted = Vec::new();
+        if new_cap < self.len() {
+            while self.len() > new_cap {
+                match self.map.pop_front() {
+                    Some((k, (_, v))) => evicted.push((k, v)),
+                    None => break,
+                }
+            }
+        }
+        self.capacity = new_cap;
+        evicted
+    }
+
+    /// Drop every entry that has outlived the cache's TTL, returning them.
+    /// A no-op, returning an empty `Vec`, if the cache wasn't built with a
+    /// TTL.
+    pub fn purge_expired(&mut self) -> Vec<(K, V)>
+    where
+        K: Clone,
+    {
+        let ttl = match self.ttl {
+            Some(ttl) => ttl,
+            None => return Vec::new(),
+        };
+        let stale: Vec<K> = self
+            .map
+            .iter()
+            .filter(|(_, (inserted, _))| inserted.elapsed() >= ttl)
+            .map(|(k, _)| k.clone())
+            .collect();
+        stale
+            .into_iter()
+            .filter_map(|k| self.map.remove(&k))
+            .map(|(k, (_, v))| (k, v))
+            .collect()
+    }
+
+    /// The remaining lifetime of `key`'s entry, or `None` if it's absent or
+    /// the cache has no TTL configured.
+    pub fn peek_ttl<Q: ?Sized>(&self, key: &Q) -> Option<Duration>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        let ttl = self.ttl?;
+        let (inserted, _) = self.map.get(key)?;
+        Some(ttl.saturating_sub(inserted.elapsed()))
+    }
+
     #[inline]
     pub fn hits_ratio(&self) -> f64 {
         self.hits as f64 / (self.hits + self.misses) as f64
@@ -143,28 +271,21 @@ where
 
     #[inline]
     pub fn front(&self) -> Option<&V> {
-        self.map.front().map(|(_, v)| v)
+        self.map.front().map(|(_, (_, v))| v)
     }
 
     #[inline]
     pub fn back(&self) -> Option<&V> {
-        self.map.back().map(|(_, v)| v)
+        self.map.back().map(|(_, (_, v))| v)
     }
 
     #[inline]
     pub fn pos(&self, pos: usize) -> Option<&V> {
-        self.map.position(pos).map(|(_, v)| v)
+        self.map.position(pos).map(|(_, (_, v))| v)
     }
 }
 
-impl<K, V> Default for FIFOCache<K, V> {
-    fn default() -> Self {
-        FIFOCache {
-            capacity: 0,
-            map: Default::default(),
-
-            hits: 0,
-            misses: 0,
-        }
-    }
-}
+// No `Default` impl: every constructor requires a `NonZeroUsize` capacity,
+// and there's no sensible capacity to fall back to for a zero-arg `default()`
+// that wouldn't just reopen the panic-on-first-`put` hole that constructor
+// bound exists to close.
@@ -1,10 +1,11 @@
 pub mod fifo;
+pub mod linked_map;
 pub mod lru_1;
-//pub mod lru_2q;
+pub mod lru_2q;
 
 #[cfg(test)]
 mod tests {
-    use crate::lru_1::{LruCache, PutStrategy};
+    use crate::lru_1::LruCache;
 
     macro_rules! cache {
         ($cache:expr, { $($k:expr => $v:expr),* }) => {
@@ -31,12 +32,66 @@ mod tests {
         assert_eq!(lru_cache.get("3"), None);
     }
 
+    #[test]
+    fn test_lru_set_capacity() {
+        let mut lru_cache = LruCache::new(3);
+        cache!(lru_cache, {
+            1 => "a",
+            2 => "b",
+            3 => "c"
+        });
+
+        let evicted = lru_cache.set_capacity(1);
+        assert_eq!(evicted, vec![(1, "a"), (2, "b")]);
+        assert_eq!(lru_cache.len(), 1);
+        assert_eq!(lru_cache.capacity(), 1);
+
+        assert_eq!(lru_cache.set_capacity(3), vec![]);
+        lru_cache.put(4, "d");
+        lru_cache.put(5, "e");
+        assert_eq!(lru_cache.len(), 3);
+    }
+
+    #[test]
+    fn test_lru_set_capacity_clamps_to_one() {
+        let mut lru_cache = LruCache::new(2);
+        lru_cache.put(1, "a");
+        lru_cache.set_capacity(0);
+        assert_eq!(lru_cache.capacity(), 1);
+        lru_cache.put(2, "b");
+        lru_cache.put(3, "c");
+        assert_eq!(lru_cache.len(), 1);
+    }
+
+    #[test]
+    fn test_lru_get_or_insert_with() {
+        let mut lru_cache = LruCache::new(2);
+        assert_eq!(*lru_cache.get_or_insert_with(1, || "a"), "a");
+        assert_eq!(*lru_cache.get_or_insert_with(1, || "never"), "a");
+        assert_eq!(lru_cache.len(), 1);
+    }
+
+    #[test]
+    fn test_lru_put_or_modify() {
+        let mut lru_cache: LruCache<i32, i32> = LruCache::new(2);
+        lru_cache.put_or_modify(1, || 1, |v| *v += 1);
+        assert_eq!(lru_cache.get(&1), Some(&1));
+        lru_cache.put_or_modify(1, || 100, |v| *v += 1);
+        assert_eq!(lru_cache.get(&1), Some(&2));
+    }
+
     mod test_fifi {
+        use std::num::NonZeroUsize;
+
         use crate::fifo::FIFOCache;
 
+        fn cap(n: usize) -> NonZeroUsize {
+            NonZeroUsize::new(n).unwrap()
+        }
+
         #[test]
         fn test_put_get() {
-            let mut fifo_cache = FIFOCache::new(3);
+            let mut fifo_cache = FIFOCache::new(cap(3));
             cache!(fifo_cache, {
                 1 => "a",
                 2 => "b",
@@ -52,15 +107,15 @@ mod tests {
         }
 
         #[test]
-        #[should_panic]
         fn test_out_of_capacity() {
-            let mut fifo_cache = FIFOCache::new(0);
-            fifo_cache.put(1, "a");
+            // A zero capacity is now rejected by the type system at
+            // construction time, instead of panicking on the first `put`.
+            assert_eq!(NonZeroUsize::new(0), None);
         }
 
         #[test]
         fn test_hits_ratio() {
-            let mut fifo_cache = FIFOCache::new(2);
+            let mut fifo_cache = FIFOCache::new(cap(2));
             cache!(fifo_cache, {
                 1 => "a",
                 2 => "b"
@@ -74,7 +129,7 @@ mod tests {
 
         #[test]
         fn test_renew() {
-            let mut fifo_cache = FIFOCache::new(2);
+            let mut fifo_cache = FIFOCache::new(cap(2));
             cache!(fifo_cache, {
                 1 => "a",
                 2 => "b"
@@ -86,7 +141,7 @@ mod tests {
 
         #[test]
         fn test_take() {
-            let mut fifo_cache = FIFOCache::new(3);
+            let mut fifo_cache = FIFOCache::new(cap(3));
             cache!(fifo_cache, {
                 1 => "a",
                 2 => "b"
@@ -97,7 +152,7 @@ mod tests {
 
         #[test]
         fn test_other() {
-            let mut fifo_cache = FIFOCache::new(3);
+            let mut fifo_cache = FIFOCache::new(cap(3));
             cache!(fifo_cache, {
                 1 => "a",
                 2 => "b"
@@ -111,5 +166,197 @@ mod tests {
             assert_eq!(fifo_cache.len(), 0);
             assert_eq!(fifo_cache.capacity(), 3);
         }
+
+        #[test]
+        fn test_set_capacity() {
+            let mut fifo_cache = FIFOCache::new(cap(3));
+            cache!(fifo_cache, {
+                1 => "a",
+                2 => "b",
+                3 => "c"
+            });
+
+            let evicted = fifo_cache.set_capacity(cap(1));
+            assert_eq!(evicted, vec![(1, "a"), (2, "b")]);
+            assert_eq!(fifo_cache.len(), 1);
+            assert_eq!(fifo_cache.capacity(), 1);
+
+            assert_eq!(fifo_cache.set_capacity(cap(3)), vec![]);
+            fifo_cache.put(4, "d");
+            fifo_cache.put(5, "e");
+            assert_eq!(fifo_cache.len(), 3);
+        }
+
+        #[test]
+        fn test_set_capacity_rejects_zero() {
+            // Like construction, `set_capacity` takes a `NonZeroUsize`, so a
+            // zero capacity can't silently reopen the `debug_assert!` panic
+            // that `new`'s `NonZeroUsize` bound was meant to close off.
+            assert_eq!(NonZeroUsize::new(0), None);
+        }
+    }
+
+    mod test_ttl {
+        use std::num::NonZeroUsize;
+        use std::thread::sleep;
+        use std::time::Duration;
+
+        use crate::fifo::FIFOCache;
+        use crate::lru_1::LruCache;
+
+        #[test]
+        fn test_fifo_ttl_expires() {
+            let mut cache = FIFOCache::with_ttl(NonZeroUsize::new(2).unwrap(), Duration::from_millis(20));
+            cache.put(1, "a");
+            assert_eq!(cache.get(&1), Some(&"a"));
+            sleep(Duration::from_millis(40));
+            assert_eq!(cache.get(&1), None);
+        }
+
+        #[test]
+        fn test_fifo_purge_expired() {
+            let mut cache = FIFOCache::with_ttl(NonZeroUsize::new(2).unwrap(), Duration::from_millis(20));
+            cache.put(1, "a");
+            sleep(Duration::from_millis(40));
+            assert_eq!(cache.purge_expired(), vec![(1, "a")]);
+            assert!(cache.is_empty());
+        }
+
+        #[test]
+        fn test_lru_ttl_expires() {
+            let mut cache = LruCache::with_ttl(2, Duration::from_millis(20));
+            cache.put(1, "a");
+            assert_eq!(cache.get(&1), Some(&"a"));
+            sleep(Duration::from_millis(40));
+            assert_eq!(cache.get(&1), None);
+        }
+
+        #[test]
+        fn test_peek_ttl() {
+            let mut cache = FIFOCache::with_ttl(NonZeroUsize::new(2).unwrap(), Duration::from_millis(50));
+            cache.put(1, "a");
+            let remaining = cache.peek_ttl(&1).unwrap();
+            assert!(remaining <= Duration::from_millis(50));
+        }
+    }
+
+    mod test_weight {
+        use crate::lru_1::{LruCache, PutStrategy, WeightScale};
+
+        struct Len;
+
+        impl WeightScale<i32, String> for Len {
+            fn weight(&self, _key: &i32, value: &String) -> usize {
+                value.len()
+            }
+        }
+
+        #[test]
+        fn test_put_with_weight_evicts_by_weight() {
+            let mut cache = LruCache::with_scale(10, Len);
+            cache.put_with_weight(1, "aaaaa".to_owned()).unwrap(); // weight 5
+            cache.put_with_weight(2, "bbb".to_owned()).unwrap(); // weight 3, total 8
+            assert_eq!(cache.weight(), 8);
+
+            // Updating key 1 with a heavier value must evict key 2 to make
+            // room without underflowing the tracked weight (regression for
+            // a double-eviction bug when the updated key sat at the front).
+            let evicted = cache.put_with_weight(1, "cccccccc".to_owned()).unwrap();
+            assert_eq!(evicted, vec![(2, "bbb".to_owned())]);
+            assert_eq!(cache.weight(), 8);
+            assert_eq!(cache.len(), 1);
+            assert_eq!(cache.get(&1), Some(&"cccccccc".to_owned()));
+        }
+
+        #[test]
+        fn test_put_with_weight_move_skips_capacity_check() {
+            let mut cache = LruCache::with_scale(10, Len);
+            cache.put_with_weight(1, "aaaaa".to_owned()).unwrap(); // weight 5
+            cache.put_strategy(PutStrategy::Move);
+
+            // Move never stores the incoming value, so an oversized one
+            // must not be rejected the way it would be for Add/Replace.
+            let res = cache.put_with_weight(1, "way-too-large-for-capacity".to_owned());
+            assert!(res.is_ok());
+            assert_eq!(cache.weight(), 5);
+            assert_eq!(cache.get(&1), Some(&"aaaaa".to_owned()));
+        }
+
+        #[test]
+        fn test_put_with_weight_replace_keeps_position() {
+            let mut cache = LruCache::with_scale(10, Len);
+            cache.put_strategy(PutStrategy::Replace);
+            cache.put_with_weight(1, "aa".to_owned()).unwrap(); // weight 2, front
+            cache.put_with_weight(2, "bb".to_owned()).unwrap(); // weight 2, back
+
+            // Replace must update key 1 in place, the same way the
+            // unweighted `put` does, instead of moving it to the back.
+            cache.put_with_weight(1, "ccc".to_owned()).unwrap();
+            assert_eq!(cache.front(), Some(&"ccc".to_owned()));
+            assert_eq!(cache.back(), Some(&"bb".to_owned()));
+            assert_eq!(cache.weight(), 5);
+        }
+    }
+
+    mod test_hasher {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::BuildHasherDefault;
+        use std::num::NonZeroUsize;
+
+        use crate::fifo::FIFOCache;
+        use crate::lru_1::{LruCache, NoWeight};
+
+        type Fnv = BuildHasherDefault<DefaultHasher>;
+
+        #[test]
+        fn test_fifo_with_custom_hasher() {
+            let mut cache = FIFOCache::with_hasher(NonZeroUsize::new(2).unwrap(), Fnv::default());
+            cache.put(1, "a");
+            cache.put(2, "b");
+            assert_eq!(cache.get(&1), Some(&"a"));
+        }
+
+        #[test]
+        fn test_lru_with_custom_hasher() {
+            let mut cache: LruCache<i32, &str, NoWeight, Fnv> =
+                LruCache::with_hasher(2, Fnv::default());
+            cache.put(1, "a");
+            cache.put(2, "b");
+            assert_eq!(cache.get(&1), Some(&"a"));
+        }
+    }
+
+    mod test_lru2q {
+        use crate::lru_2q::Lru2qCache;
+
+        #[test]
+        fn test_admission() {
+            // kin = 1, kout = 1: A1in holds a single "seen once" entry
+            // before spilling its key into the A1out ghost queue.
+            let mut cache = Lru2qCache::with_kin_kout(4, 1, 1);
+
+            cache.put(1, "a");
+            assert_eq!(cache.get(&1), Some(&"a"));
+
+            // Evicts 1 out of A1in into the A1out ghost queue.
+            cache.put(2, "b");
+            assert_eq!(cache.get(&1), None);
+            assert_eq!(cache.get(&2), Some(&"b"));
+
+            // 1 is recognised via the ghost queue as having been seen
+            // before, so it's admitted straight into Am instead of A1in.
+            cache.put(1, "aa");
+            assert_eq!(cache.get(&1), Some(&"aa"));
+        }
+
+        #[test]
+        fn test_with_kin_kout_clamps_zero() {
+            // kin/kout of 0 must be clamped to 1 rather than panicking in
+            // the NonZeroUsize::new(..).unwrap() calls underneath.
+            let mut cache: Lru2qCache<i32, &str> = Lru2qCache::with_kin_kout(4, 0, 0);
+            assert_eq!(cache.kin_kout(), (1, 1));
+            cache.put(1, "a");
+            assert_eq!(cache.get(&1), Some(&"a"));
+        }
     }
 }
@@ -0,0 +1,326 @@
+//! A small insertion/access-ordered map, generic over its [`BuildHasher`].
+//!
+//! This crate used to delegate directly to `linked_hash_map_rs::LinkedHashMap`,
+//! but that crate only implements its operational methods (`get`, `push_back`,
+//! `pop_front`, `move_to_back`, ...) for the default `RandomState` hasher —
+//! there's no generic-`S` impl block to call into. That made the pluggable
+//! hasher support on [`FIFOCache`](crate::fifo::FIFOCache) and
+//! [`LruCache`](crate::lru_1::LruCache) fail to compile for any hasher at
+//! all, including the default. This is a minimal hand-rolled replacement
+//! (a `HashMap` index plus an intrusive doubly linked list) that supports
+//! the same operations generically over `S`.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+use std::ptr::replace;
+
+struct KeyPtr<K> {
+    k: *const K,
+}
+
+#[derive(Hash, PartialEq, Eq)]
+#[repr(transparent)]
+struct Qey<Q: ?Sized>(Q);
+
+impl<Q: ?Sized> Qey<Q> {
+    fn from_ref(q: &Q) -> &Self {
+        unsafe { std::mem::transmute(q) }
+    }
+}
+
+impl<K, Q: ?Sized> Borrow<Qey<Q>> for KeyPtr<K>
+where
+    K: Borrow<Q>,
+{
+    fn borrow(&self) -> &Qey<Q> {
+        Qey::from_ref(unsafe { (*self.k).borrow() })
+    }
+}
+
+impl<K: Hash> Hash for KeyPtr<K> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        unsafe { (*self.k).hash(state) }
+    }
+}
+
+impl<K: PartialEq> PartialEq for KeyPtr<K> {
+    fn eq(&self, other: &Self) -> bool {
+        unsafe { (*self.k).eq(&*other.k) }
+    }
+}
+
+impl<K: Eq> Eq for KeyPtr<K> {}
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    prev: Option<*mut Node<K, V>>,
+    next: Option<*mut Node<K, V>>,
+}
+
+pub struct LinkedHashMap<K, V, S = RandomState> {
+    hash_map: HashMap<KeyPtr<K>, *mut Node<K, V>, S>,
+    head: Option<*mut Node<K, V>>,
+    tail: Option<*mut Node<K, V>>,
+}
+
+impl<K, V, S> LinkedHashMap<K, V, S> {
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        LinkedHashMap {
+            hash_map: HashMap::with_capacity_and_hasher(capacity, hasher),
+            head: None,
+            tail: None,
+        }
+    }
+}
+
+impl<K, V> LinkedHashMap<K, V, RandomState>
+where
+    K: Hash + Eq,
+{
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, RandomState::default())
+    }
+}
+
+impl<K, V, S> LinkedHashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    #[inline]
+    unsafe fn push_back_node(&mut self, node: *mut Node<K, V>) {
+        (*node).prev = self.tail;
+        (*node).next = None;
+        let node_ptr = Some(node);
+        if let Some(tail) = self.tail {
+            (*tail).next = node_ptr
+        } else {
+            self.head = node_ptr;
+        }
+        self.tail = node_ptr;
+    }
+
+    #[inline]
+    fn remove_node(&mut self, node: *mut Node<K, V>) {
+        unsafe {
+            if let Some(head) = self.head {
+                if head == node {
+                    self.head = (*head).next
+                }
+            }
+            if let Some(tail) = self.tail {
+                if tail == node {
+                    self.tail = (*tail).prev
+                }
+            }
+            if let Some(next) = (*node).next {
+                (*next).prev = (*node).prev
+            }
+            if let Some(prev) = (*node).prev {
+                (*prev).next = (*node).next
+            }
+        }
+    }
+
+    /// Insert `value` at the back of the queue, or replace it in place
+    /// (without moving it) if `key` is already present.
+    #[inline]
+    pub fn push_back(&mut self, key: K, value: V) -> Option<(&K, V)> {
+        unsafe {
+            if let Some(&node) = self.hash_map.get(&KeyPtr { k: &key }) {
+                let old = replace(&mut (*node).value, value);
+                Some((&(*node).key, old))
+            } else {
+                let node = Box::into_raw(Box::new(Node {
+                    key,
+                    value,
+                    prev: None,
+                    next: None,
+                }));
+                self.hash_map.insert(KeyPtr { k: &(*node).key }, node);
+                self.push_back_node(node);
+                None
+            }
+        }
+    }
+
+    /// Like [`push_back`](Self::push_back), but return the put element
+    /// instead of the replaced one.
+    #[inline]
+    pub fn push_back_and_return(&mut self, key: K, value: V) -> (&K, &V) {
+        unsafe {
+            if let Some(&node) = self.hash_map.get(&KeyPtr { k: &key }) {
+                replace(&mut (*node).value, value);
+                (&(*node).key, &(*node).value)
+            } else {
+                let node = Box::into_raw(Box::new(Node {
+                    key,
+                    value,
+                    prev: None,
+                    next: None,
+                }));
+                self.hash_map.insert(KeyPtr { k: &(*node).key }, node);
+                self.push_back_node(node);
+                (&(*node).key, &(*node).value)
+            }
+        }
+    }
+
+    #[inline]
+    pub fn pop_front(&mut self) -> Option<(K, V)> {
+        self.head.map(|node| unsafe {
+            self.head = (*node).next;
+            match self.head {
+                None => self.tail = None,
+                Some(head) => (*head).prev = None,
+            }
+            self.hash_map.remove(&KeyPtr { k: &(*node).key });
+            let node = Box::from_raw(node);
+            (node.key, node.value)
+        })
+    }
+
+    #[inline]
+    pub fn front(&self) -> Option<(&K, &V)> {
+        self.head.map(|node| unsafe { (&(*node).key, &(*node).value) })
+    }
+
+    #[inline]
+    pub fn back(&self) -> Option<(&K, &V)> {
+        self.tail.map(|node| unsafe { (&(*node).key, &(*node).value) })
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.hash_map.len()
+    }
+
+    #[inline]
+    pub fn get<Q: ?Sized + Hash + Eq>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        self.hash_map
+            .get(Qey::from_ref(key))
+            .map(|&node| unsafe { &(*node).value })
+    }
+
+    #[inline]
+    pub fn get_mut<Q: ?Sized + Hash + Eq>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+    {
+        self.hash_map
+            .get_mut(Qey::from_ref(key))
+            .map(|&mut node| unsafe { &mut (*node).value })
+    }
+
+    #[inline]
+    pub fn remove<Q: ?Sized + Hash + Eq>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+    {
+        self.hash_map.remove(Qey::from_ref(key)).map(|node| unsafe {
+            self.remove_node(node);
+            let node = Box::from_raw(node);
+            (node.key, node.value)
+        })
+    }
+
+    #[inline]
+    pub fn move_to_back<Q: ?Sized + Hash + Eq>(&mut self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+    {
+        self.hash_map
+            .get(Qey::from_ref(key))
+            .copied()
+            .map(|node| unsafe {
+                self.remove_node(node);
+                self.push_back_node(node);
+                (&(*node).key, &(*node).value)
+            })
+    }
+
+    #[inline]
+    pub fn contains<Q: ?Sized + Hash + Eq>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        self.hash_map.contains_key(Qey::from_ref(key))
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.hash_map.is_empty()
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+
+    #[inline]
+    pub fn position(&self, pos: usize) -> Option<(&K, &V)> {
+        let mut next = self.head;
+        for _ in 0..pos {
+            next = next.and_then(|node| unsafe { (*node).next });
+        }
+        next.map(|node| unsafe { (&(*node).key, &(*node).value) })
+    }
+
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            next: self.head.map(|ptr| ptr as *const _),
+            marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<K, V, S: Default> Default for LinkedHashMap<K, V, S> {
+    fn default() -> Self {
+        LinkedHashMap {
+            hash_map: HashMap::default(),
+            head: None,
+            tail: None,
+        }
+    }
+}
+
+impl<K, V, S> Drop for LinkedHashMap<K, V, S> {
+    fn drop(&mut self) {
+        unsafe fn drop_node<K, V>(node: *mut Node<K, V>) {
+            let node = Box::from_raw(node);
+            if let Some(next) = node.next {
+                drop_node(next)
+            }
+        }
+        if let Some(node) = self.head {
+            unsafe { drop_node(node) }
+        }
+    }
+}
+
+pub struct Iter<'a, K, V> {
+    next: Option<*const Node<K, V>>,
+    marker: std::marker::PhantomData<(&'a K, &'a V)>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.map(|node| unsafe {
+            let kv = (&(*node).key, &(*node).value);
+            self.next = (*node).next.map(|ptr| ptr as *const _);
+            kv
+        })
+    }
+}
+
+unsafe impl<K: Send, V: Send, S> Send for LinkedHashMap<K, V, S> {}
+unsafe impl<K: Sync, V: Sync, S> Sync for LinkedHashMap<K, V, S> {}
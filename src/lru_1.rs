@@ -1,14 +1,36 @@
 use std::borrow::Borrow;
-use std::hash::Hash;
-
-use linked_hash_map_rs::LinkedHashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
 use std::ops::Deref;
+use std::time::{Duration, Instant};
+
+use crate::linked_map::LinkedHashMap;
+
+/// Assigns a weight to a key/value pair, used by [`LruCache::put_with_weight`]
+/// to bound the cache by total weight instead of entry count.
+pub trait WeightScale<K, V> {
+    fn weight(&self, key: &K, value: &V) -> usize;
+}
 
-pub struct LruCache<K, V> {
+/// The default [`WeightScale`]: every entry weighs nothing, so
+/// [`LruCache::put`] keeps bounding the cache purely by entry count.
+#[derive(Default)]
+pub struct NoWeight;
+
+impl<K, V> WeightScale<K, V> for NoWeight {
+    fn weight(&self, _key: &K, _value: &V) -> usize {
+        0
+    }
+}
+
+pub struct LruCache<K, V, W = NoWeight, S = RandomState> {
     capacity: usize,
-    map: LinkedHashMap<K, V>,
+    map: LinkedHashMap<K, (Instant, V), S>,
     put_strategy: PutStrategy,
     popped_count: usize,
+    weight: usize,
+    scale: W,
+    ttl: Option<Duration>,
 }
 
 pub enum PutStrategy {
@@ -26,38 +48,104 @@ impl Default for PutStrategy {
     }
 }
 
-impl<K, V> LruCache<K, V>
+impl<K, V> LruCache<K, V, NoWeight, RandomState>
 where
     K: Hash + Eq,
 {
     pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, RandomState::default())
+    }
+
+    pub fn with_put_strategy(capacity: usize, strategy: PutStrategy) -> Self {
+        Self::with_hasher_and_strategy(capacity, strategy, RandomState::default())
+    }
+
+    /// Create a cache whose entries expire `ttl` after they're inserted (or
+    /// last replaced/added back, per [`PutStrategy`]). A `get`/`get_mut`
+    /// against a stale entry is treated as a miss and the entry is dropped.
+    pub fn with_ttl(capacity: usize, ttl: Duration) -> Self {
+        let mut this = Self::new(capacity);
+        this.ttl = Some(ttl);
+        this
+    }
+}
+
+impl<K, V, W> LruCache<K, V, W, RandomState>
+where
+    K: Hash + Eq,
+{
+    /// Create a cache bounded by total weight, as assigned by `scale`.
+    pub fn with_scale(capacity: usize, scale: W) -> Self {
         LruCache {
             capacity,
             map: LinkedHashMap::with_capacity(capacity),
-            ..Default::default()
+            put_strategy: Default::default(),
+            popped_count: 0,
+            weight: 0,
+            scale,
+            ttl: None,
         }
     }
+}
 
-    pub fn with_put_strategy(capacity: usize, strategy: PutStrategy) -> Self {
+impl<K, V, W, S> LruCache<K, V, W, S>
+where
+    K: Hash + Eq,
+    W: Default,
+    S: BuildHasher,
+{
+    /// Create a cache using a custom [`BuildHasher`], e.g. to drop in a
+    /// faster or DoS-resistant hasher for hot-path lookups.
+    pub fn with_hasher(capacity: usize, hasher: S) -> Self {
         LruCache {
             capacity,
-            map: LinkedHashMap::with_capacity(capacity),
-            put_strategy: strategy,
-            ..Default::default()
+            map: LinkedHashMap::with_capacity_and_hasher(capacity, hasher),
+            put_strategy: Default::default(),
+            popped_count: 0,
+            weight: 0,
+            scale: Default::default(),
+            ttl: None,
         }
     }
 
+    /// Like [`with_hasher`](Self::with_hasher), but also sets the initial
+    /// [`PutStrategy`].
+    pub fn with_hasher_and_strategy(capacity: usize, strategy: PutStrategy, hasher: S) -> Self {
+        let mut this = Self::with_hasher(capacity, hasher);
+        this.put_strategy = strategy;
+        this
+    }
+}
+
+impl<K, V, W, S> LruCache<K, V, W, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
     pub fn put_strategy(&mut self, strategy: PutStrategy) {
         self.put_strategy = strategy;
     }
 
+    #[inline]
+    fn is_expired(&self, inserted: Instant) -> bool {
+        matches!(self.ttl, Some(ttl) if inserted.elapsed() >= ttl)
+    }
+
     #[inline]
     pub fn get<Q: ?Sized>(&mut self, key: &Q) -> Option<&V>
     where
         K: Borrow<Q>,
         Q: Hash + Eq,
     {
-        self.map.move_to_back(key).map(|(_, v)| v)
+        let expired = match self.map.get(key) {
+            Some((inserted, _)) => self.is_expired(*inserted),
+            None => return None,
+        };
+        if expired {
+            self.map.remove(key);
+            return None;
+        }
+        self.map.move_to_back(key).map(|(_, (_, v))| v)
     }
 
     #[inline]
@@ -66,13 +154,82 @@ where
         K: Borrow<Q>,
         Q: Hash + Eq,
     {
+        let expired = match self.map.get(key) {
+            Some((inserted, _)) => self.is_expired(*inserted),
+            None => return None,
+        };
+        if expired {
+            self.map.remove(key);
+            return None;
+        }
         if self.map.move_to_back(key).is_some() {
-            self.map.get_mut(key)
+            self.map.get_mut(key).map(|(_, v)| v)
         } else {
             None
         }
     }
 
+    /// Get the value for `key`, moving it to the back of the queue, or
+    /// insert the value produced by `default` if it isn't present.
+    ///
+    /// Replaces the `contains` + `get`/`put` sequence callers otherwise
+    /// need. The key is still looked up more than once internally: once to
+    /// check for it, and again to move or insert it.
+    #[inline]
+    pub fn get_or_insert_with<F>(&mut self, key: K, default: F) -> &V
+    where
+        F: FnOnce() -> V,
+    {
+        let present = match self.map.get(&key) {
+            Some((inserted, _)) if self.is_expired(*inserted) => {
+                self.map.remove(&key);
+                false
+            },
+            Some(_) => true,
+            None => false,
+        };
+        if present {
+            self.map.move_to_back(&key).map(|(_, (_, v))| v).unwrap()
+        } else {
+            if self.len() >= self.capacity() {
+                self.map.pop_front();
+                self.popped_count += 1;
+            }
+            let (_, (_, v)) = self.map.push_back_and_return(key, (Instant::now(), default()));
+            v
+        }
+    }
+
+    /// Insert a new value produced by `on_insert`, or apply `on_modify` to
+    /// the existing value in place, moving it to the back of the queue.
+    #[inline]
+    pub fn put_or_modify<FI, FM>(&mut self, key: K, on_insert: FI, mut on_modify: FM)
+    where
+        FI: FnOnce() -> V,
+        FM: FnMut(&mut V),
+    {
+        let present = match self.map.get(&key) {
+            Some((inserted, _)) if self.is_expired(*inserted) => {
+                self.map.remove(&key);
+                false
+            },
+            Some(_) => true,
+            None => false,
+        };
+        if present {
+            self.map.move_to_back(&key);
+            if let Some((_, v)) = self.map.get_mut(&key) {
+                on_modify(v);
+            }
+        } else {
+            if self.len() >= self.capacity() {
+                self.map.pop_front();
+                self.popped_count += 1;
+            }
+            self.map.push_back(key, (Instant::now(), on_insert()));
+        }
+    }
+
     /// Take an element out of the cache
     #[inline]
     pub fn take<Q: ?Sized>(&mut self, key: &Q) -> Option<(K, V)>
@@ -80,9 +237,206 @@ where
         K: Borrow<Q>,
         Q: Hash + Eq,
     {
-        self.map.remove(key)
+        self.map.remove(key).map(|(k, (_, v))| (k, v))
+    }
+
+    /// Get the actual size of the cache
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Get the capacity of the cache
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get the number of elements that have been popped
+    #[inline]
+    pub fn popped_count(&self) -> usize {
+        self.popped_count
+    }
+
+    /// Change the capacity of the cache.
+    ///
+    /// Shrinking pops entries from the LRU end (the oldest ones) until the
+    /// cache fits the new capacity, returning whatever got evicted. Growing
+    /// just raises the bound; the map grows on its own as entries are added.
+    ///
+    /// `new_cap` is clamped to at least 1: a zero capacity would otherwise
+    /// re-trigger the `debug_assert!` in [`put`](Self::put) on the very next
+    /// insert.
+    pub fn set_capacity(&mut self, new_cap: usize) -> Vec<(K, V)> {
+        let new_cap = new_cap.max(1);
+        let mut evicted = Vec::new();
+        if new_cap < self.len() {
+            while self.len() > new_cap {
+                match self.map.pop_front() {
+                    Some((k, (_, v))) => {
+                        self.popped_count += 1;
+                        evicted.push((k, v));
+                    },
+                    None => break,
+                }
+            }
+        }
+        self.capacity = new_cap;
+        evicted
+    }
+
+    /// Drop every entry that has outlived the cache's TTL, returning them.
+    /// A no-op, returning an empty `Vec`, if the cache wasn't built with a
+    /// TTL.
+    pub fn purge_expired(&mut self) -> Vec<(K, V)>
+    where
+        K: Clone,
+    {
+        let ttl = match self.ttl {
+            Some(ttl) => ttl,
+            None => return Vec::new(),
+        };
+        let stale: Vec<K> = self
+            .map
+            .iter()
+            .filter(|(_, (inserted, _))| inserted.elapsed() >= ttl)
+            .map(|(k, _)| k.clone())
+            .collect();
+        stale
+            .into_iter()
+            .filter_map(|k| self.map.remove(&k))
+            .map(|(k, (_, v))| (k, v))
+            .collect()
+    }
+
+    /// The remaining lifetime of `key`'s entry, or `None` if it's absent or
+    /// the cache has no TTL configured.
+    pub fn peek_ttl<Q: ?Sized>(&self, key: &Q) -> Option<Duration>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        let ttl = self.ttl?;
+        let (inserted, _) = self.map.get(key)?;
+        Some(ttl.saturating_sub(inserted.elapsed()))
+    }
+
+    /// Get the total weight of the entries currently held, as tracked by
+    /// [`put_with_weight`](Self::put_with_weight). Zero for a cache that
+    /// only ever uses [`put`](Self::put).
+    #[inline]
+    pub fn weight(&self) -> usize {
+        self.weight
+    }
+
+    /// Put an element, bounding the cache by total weight (as assigned by
+    /// `W`) instead of by entry count.
+    ///
+    /// Entries are evicted from the front, oldest first, until the new
+    /// entry fits. If the entry's own weight exceeds `capacity`, it can
+    /// never fit and is rejected, handing the key/value back unchanged.
+    /// `PutStrategy::Move` leaves an existing entry untouched (no weight
+    /// check, no eviction); `Add` moves an updated key to the back, while
+    /// `PutStrategy::Replace` updates it in place without moving it, same
+    /// as the unweighted [`put`](Self::put).
+    #[inline]
+    pub fn put_with_weight(&mut self, key: K, value: V) -> Result<Vec<(K, V)>, (K, V)>
+    where
+        W: WeightScale<K, V>,
+    {
+        // `Move` never stores `value`, so it neither needs `value` to fit
+        // the weight budget nor changes the tracked weight at all.
+        if matches!(self.put_strategy, PutStrategy::Move) && self.map.contains(&key) {
+            self.map.move_to_back(&key);
+            return Ok(Vec::new());
+        }
+
+        let new_weight = self.scale.weight(&key, &value);
+        if new_weight > self.capacity {
+            return Err((key, value));
+        }
+
+        // `Replace` keeps the key at its current position, so it must be
+        // protected from `evict_until_fits` (which would otherwise be free
+        // to pop it if it happens to be the LRU-oldest entry) and must not
+        // be removed from the map the way `Add`'s move-to-back does.
+        let replace_in_place = matches!(self.put_strategy, PutStrategy::Replace) && self.map.contains(&key);
+
+        let mut evicted = Vec::new();
+        if let Some((_, old)) = self.map.get(&key) {
+            self.weight -= self.scale.weight(&key, old);
+            if !replace_in_place {
+                self.map.remove(&key);
+            }
+        }
+        let protect = replace_in_place.then_some(&key);
+        self.evict_until_fits(new_weight, &mut evicted, protect);
+        self.map.push_back(key, (Instant::now(), value));
+        self.weight += new_weight;
+        Ok(evicted)
+    }
+
+    #[inline]
+    fn evict_until_fits(&mut self, incoming_weight: usize, evicted: &mut Vec<(K, V)>, protect: Option<&K>)
+    where
+        W: WeightScale<K, V>,
+    {
+        while self.weight + incoming_weight > self.capacity {
+            if let (Some(protect), Some((front_key, _))) = (protect, self.map.front()) {
+                // The protected key is the oldest entry and nothing else is
+                // eligible for eviction ahead of it; give up rather than
+                // evict the entry we're meant to be updating in place.
+                if front_key == protect {
+                    break;
+                }
+            }
+            match self.map.pop_front() {
+                Some((k, (_, v))) => {
+                    self.weight -= self.scale.weight(&k, &v);
+                    self.popped_count += 1;
+                    evicted.push((k, v));
+                },
+                None => break,
+            }
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.map.clear()
+    }
+
+    #[inline]
+    pub fn front(&self) -> Option<&V> {
+        self.map.front().map(|(_, (_, v))| v)
+    }
+
+    #[inline]
+    pub fn back(&self) -> Option<&V> {
+        self.map.back().map(|(_, (_, v))| v)
+    }
+
+    #[inline]
+    pub fn pos(&self, pos: usize) -> Option<&V> {
+        self.map.position(pos).map(|(_, (_, v))| v)
     }
+}
 
+// `put`/`put_and_return` are only defined for `NoWeight`: they bound the
+// cache purely by entry count and never touch `weight`, so allowing them on
+// a cache constructed with a real `WeightScale` would let a caller bypass
+// the weight budget entirely and leave `weight()` out of sync with what the
+// cache actually holds. Use `put_with_weight` for a weighted cache instead.
+impl<K, V, S> LruCache<K, V, NoWeight, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
     /// Put an element
     /// If an element is popped, return it
     ///
@@ -94,21 +448,21 @@ where
             match self.put_strategy {
                 PutStrategy::Add => {
                     self.map.remove(&key);
-                    self.map.push_back(key, value);
+                    self.map.push_back(key, (Instant::now(), value));
                 },
                 PutStrategy::Move => {
                     self.map.move_to_back(&key);
                 },
                 PutStrategy::Replace => {
-                    self.map.push_back(key, value);
+                    self.map.push_back(key, (Instant::now(), value));
                 }
             }
         } else {
             if self.len() >= self.capacity() {
-                res = self.map.pop_front();
+                res = self.map.pop_front().map(|(k, (_, v))| (k, v));
                 self.popped_count += 1;
             };
-            self.map.push_back(key, value);
+            self.map.push_back(key, (Instant::now(), value));
         }
         debug_assert!(self.len() <= self.capacity());
         res
@@ -125,13 +479,13 @@ where
             match self.put_strategy {
                 PutStrategy::Add => {
                     self.map.remove(&key);
-                    Some(self.map.push_back_and_return(key, value))
+                    Some(self.map.push_back_and_return(key, (Instant::now(), value)))
                 },
                 PutStrategy::Move => {
                     self.map.move_to_back(&key)
                 },
                 PutStrategy::Replace => {
-                    Some(self.map.push_back_and_return(key, value))
+                    Some(self.map.push_back_and_return(key, (Instant::now(), value)))
                 }
             }
         } else {
@@ -139,70 +493,29 @@ where
                 self.map.pop_front();
                 self.popped_count += 1;
             };
-            self.map.push_back(key, value);
+            self.map.push_back(key, (Instant::now(), value));
             None
         };
-        res
-    }
-
-    /// Get the actual size of the cache
-    #[inline]
-    pub fn len(&self) -> usize {
-        self.map.len()
-    }
-
-    /// Get the capacity of the cache
-    #[inline]
-    pub fn capacity(&self) -> usize {
-        self.capacity
-    }
-
-    /// Get the number of elements that have been popped
-    #[inline]
-    pub fn popped_count(&self) -> usize {
-        self.popped_count
-    }
-
-    #[inline]
-    pub fn is_empty(&self) -> bool {
-        self.map.is_empty()
-    }
-
-    #[inline]
-    pub fn clear(&mut self) {
-        self.map.clear()
-    }
-
-    #[inline]
-    pub fn front(&self) -> Option<&V> {
-        self.map.front().map(|(_, v)| v)
-    }
-
-    #[inline]
-    pub fn back(&self) -> Option<&V> {
-        self.map.back().map(|(_, v)| v)
-    }
-
-    #[inline]
-    pub fn pos(&self, pos: usize) -> Option<&V> {
-        self.map.position(pos).map(|(_, v)| v)
+        res.map(|(k, (_, v))| (k, v))
     }
 }
 
-
-impl<K, V> Default for LruCache<K, V> {
+impl<K, V, W: Default, S: Default> Default for LruCache<K, V, W, S> {
     fn default() -> Self {
         LruCache {
             capacity: 0,
             map: Default::default(),
             put_strategy: Default::default(),
-            popped_count: 0
+            popped_count: 0,
+            weight: 0,
+            scale: Default::default(),
+            ttl: None,
         }
     }
 }
 
-impl<K, V> Deref for LruCache<K, V> {
-    type Target = LinkedHashMap<K, V>;
+impl<K, V, W, S> Deref for LruCache<K, V, W, S> {
+    type Target = LinkedHashMap<K, (Instant, V), S>;
 
     fn deref(&self) -> &Self::Target {
         &self.map
@@ -1,37 +1,100 @@
 use std::borrow::Borrow;
-use std::hash::Hash;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::num::NonZeroUsize;
 
 use crate::fifo::FIFOCache;
 use crate::lru_1::LruCache;
 
-pub struct Lru2qCache<K, V> {
+/// A 2Q cache, following the admission scheme described by Johnson & Shasha.
+///
+/// Three structures are kept:
+/// - `A1in`: a FIFO of items seen only once recently (`FIFOCache`).
+/// - `A1out`: a ghost queue remembering *keys only* of items recently
+///   evicted from `A1in`, so a second access can be recognised as "hot"
+///   even though its value is long gone.
+/// - `Am`: the main LRU queue for items that have proven themselves popular
+///   (`LruCache`).
+pub struct Lru2qCache<K, V, S = RandomState> {
     capacity: usize,
-    lru: LruCache<K, V>,
-    fifo: FIFOCache<K, V>,
+    kin: usize,
+    kout: usize,
+    a1in: FIFOCache<K, V, S>,
+    a1out: FIFOCache<K, (), S>,
+    am: LruCache<K, V, crate::lru_1::NoWeight, S>,
 }
 
-impl<K, V> Lru2qCache<K, V>
+impl<K, V> Lru2qCache<K, V, RandomState>
 where
     K: Hash + Eq,
 {
+    /// Create a new 2Q cache with the default `Kin`/`Kout` split: `Kin` is
+    /// ~25% of `capacity` and `Kout` is ~50% of `capacity`.
     pub fn new(capacity: usize) -> Self {
+        let kin = (capacity / 4).max(1);
+        let kout = (capacity / 2).max(1);
+        Self::with_kin_kout(capacity, kin, kout)
+    }
+
+    /// Create a new 2Q cache with explicit `Kin` (size of the `A1in` FIFO)
+    /// and `Kout` (size of the `A1out` ghost queue) bounds.
+    ///
+    /// `Am`, the main LRU, is sized to whatever's left of `capacity` once
+    /// `Kin` is set aside. `kin`/`kout` are clamped to at least 1, the same
+    /// way [`new`](Self::new) derives them, instead of panicking on a
+    /// caller-supplied 0.
+    pub fn with_kin_kout(capacity: usize, kin: usize, kout: usize) -> Self {
+        let kin = kin.max(1);
+        let kout = kout.max(1);
         Lru2qCache {
             capacity,
-            lru: LruCache::new(capacity),
-            fifo: FIFOCache::new(capacity),
+            kin,
+            kout,
+            a1in: FIFOCache::new(NonZeroUsize::new(kin).unwrap()),
+            a1out: FIFOCache::new(NonZeroUsize::new(kout).unwrap()),
+            am: LruCache::new(capacity.saturating_sub(kin).max(1)),
         }
     }
+}
 
+impl<K, V, S> Lru2qCache<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Clone,
+{
+    /// Create a new 2Q cache using a custom [`BuildHasher`] for all three of
+    /// its internal structures.
+    pub fn with_hasher(capacity: usize, hasher: S) -> Self {
+        let kin = (capacity / 4).max(1);
+        let kout = (capacity / 2).max(1);
+        Lru2qCache {
+            capacity,
+            kin,
+            kout,
+            a1in: FIFOCache::with_hasher(NonZeroUsize::new(kin).unwrap(), hasher.clone()),
+            a1out: FIFOCache::with_hasher(NonZeroUsize::new(kout).unwrap(), hasher.clone()),
+            am: LruCache::with_hasher(capacity.saturating_sub(kin).max(1), hasher),
+        }
+    }
+}
+
+impl<K, V, S> Lru2qCache<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
     #[inline]
-    pub fn get<Q: ?Sized>(&mut self, key: &Q) -> Option<&V>
+    pub fn get<Q: ?Sized + Hash + Eq>(&mut self, key: &Q) -> Option<&V>
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
     {
-        self.fifo
-            .take(key)
-            .map(move |(k, v)| self.lru.put_and_return(k, v).map(|(_, v)| v))
-            .flatten()
+        if self.am.contains(key) {
+            self.am.get(key)
+        } else {
+            // A hit in A1in doesn't promote the entry, it just confirms it
+            // wasn't accessed only once.
+            self.a1in.get(key)
+        }
     }
 
     #[inline]
@@ -39,7 +102,7 @@ where
     where
         F: FnOnce() -> V,
     {
-        if self.fifo.contains(&key) || self.lru.contains(&key) {
+        if self.am.contains(&key) || self.a1in.contains(&key) {
             self.get(&key)
         } else {
             self.put_and_return(key, init()).map(|(_, v)| v)
@@ -47,76 +110,124 @@ where
     }
 
     #[inline]
-    pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+    pub fn get_mut<Q: ?Sized + Hash + Eq>(&mut self, key: &Q) -> Option<&mut V>
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
     {
-        todo!()
+        if self.am.contains(key) {
+            self.am.get_mut(key)
+        } else {
+            self.a1in.get_mut(key)
+        }
     }
 
     /// Take an element out of the cache
     #[inline]
-    pub fn take<Q: ?Sized>(&mut self, key: &Q) -> Option<(K, V)>
+    pub fn take<Q: ?Sized + Hash + Eq>(&mut self, key: &Q) -> Option<(K, V)>
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
     {
-        todo!()
+        self.am.take(key).or_else(|| self.a1in.take(key))
     }
 
-    /// Put an element
-    /// If an element is popped, return it
+    /// Put an element.
     ///
-    /// strategy: [PutStrategy]
+    /// A brand-new key is inserted into `A1in`, unless it's found in the
+    /// `A1out` ghost queue, in which case it's treated as already proven hot
+    /// and goes straight into `Am`. A key already tracked by `Am` is updated
+    /// according to `Am`'s [`PutStrategy`](crate::lru_1::PutStrategy) (by
+    /// default moved to the back); one already in `A1in` is updated in
+    /// place. If an element is evicted as a result, it is returned.
     #[inline]
-    pub fn put(&mut self, key: K, value: V) -> Option<(K, V)> {
-        self.fifo.put(key, value)
+    pub fn put(&mut self, key: K, value: V) -> Option<(K, V)>
+    where
+        K: Clone,
+    {
+        if self.am.contains(&key) {
+            return self.am.put(key, value);
+        }
+        if let Some(slot) = self.a1in.get_mut(&key) {
+            // Updating a present key doesn't grow A1in, so nothing is evicted.
+            *slot = value;
+            return None;
+        }
+        if self.a1out.take(&key).is_some() {
+            return self.am.put(key, value);
+        }
+        let evicted = self.a1in.put(key, value);
+        if let Some((k, v)) = evicted {
+            self.a1out.put(k.clone(), ());
+            Some((k, v))
+        } else {
+            None
+        }
     }
 
+    /// Put an element, and return it. See [`put`](Self::put) for the
+    /// admission rules.
     #[inline]
     pub fn put_and_return(&mut self, key: K, value: V) -> Option<(&K, &V)> {
-        self.fifo.put_and_return(key, value)
+        if self.am.contains(&key) {
+            return self.am.put_and_return(key, value);
+        }
+        if self.a1in.contains(&key) {
+            return Some(self.a1in.put_and_return(key, value));
+        }
+        if self.a1out.take(&key).is_some() {
+            // The key is new to Am, so it lands at the back of its queue.
+            self.am.put(key, value);
+            return (*self.am).back().map(|(k, (_, v))| (k, v));
+        }
+        let (evicted, k, v) = self.a1in.put_capturing_evicted(key, value);
+        if let Some((ek, _)) = evicted {
+            self.a1out.put(ek, ());
+        }
+        Some((k, v))
     }
 
-    /// Get the actual size of the cache
+    /// Get the actual size of the cache (ghost keys held in `A1out` aren't
+    /// counted, since they don't carry values)
     #[inline]
     pub fn len(&self) -> usize {
-        self.fifo.len() + self.lru.len()
+        self.a1in.len() + self.am.len()
     }
 
     /// Get the capacity of the cache
     #[inline]
     pub fn capacity(&self) -> usize {
-        debug_assert!(
-            self.capacity == self.fifo.capacity() && self.capacity == self.lru.capacity()
-        );
         self.capacity
     }
 
+    /// Get the `Kin`/`Kout` bounds currently in use
+    #[inline]
+    pub fn kin_kout(&self) -> (usize, usize) {
+        (self.kin, self.kout)
+    }
+
     #[inline]
     pub fn is_empty(&self) -> bool {
-        self.fifo.is_empty() && self.lru.is_empty()
+        self.a1in.is_empty() && self.am.is_empty()
     }
 
     #[inline]
     pub fn clear(&mut self) {
-        self.fifo.clear();
-        self.lru.clear();
+        self.a1in.clear();
+        self.a1out.clear();
+        self.am.clear();
     }
 
     #[inline]
     pub fn front(&self) -> Option<&V> {
-        self.lru.front()
+        self.am.front()
     }
 
     #[inline]
     pub fn back(&self) -> Option<&V> {
-        self.fifo.back()
+        self.a1in.back()
     }
 
     #[inline]
     pub fn pos(&self, pos: usize) -> Option<&V> {
-        todo!()
+        self.am.pos(pos)
     }
 }